@@ -0,0 +1,85 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// Top-level configuration loaded from `~/.config/lambda/config.toml`.
+///
+/// The file holds any number of named launch profiles so that the
+/// `start`/`find`/`stop` commands can be driven without retyping the
+/// GPU and SSH-key strings on every invocation.
+#[derive(Deserialize, Debug, Default)]
+pub struct Config {
+    #[serde(default)]
+    pub profiles: HashMap<String, Profile>,
+    #[serde(default)]
+    pub notifications: Notifications,
+}
+
+/// Notification destinations fired by the `Find` watcher.
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct Notifications {
+    /// Send a desktop notification via `notify-send`.
+    #[serde(default)]
+    pub desktop: bool,
+    /// POST to a Slack/Discord-style webhook URL.
+    pub webhook: Option<String>,
+    /// Run a shell command hook.
+    pub command: Option<String>,
+}
+
+/// A single named profile. Every field is optional so a profile can supply
+/// just the defaults the user cares about; anything omitted falls back to an
+/// explicit flag or the environment.
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct Profile {
+    /// Default instance type used when `--gpu` is omitted.
+    pub instance_type: Option<String>,
+    /// SSH key name(s) attached to launched instances when `--ssh` is omitted.
+    #[serde(default)]
+    pub ssh_key_names: Vec<String>,
+    /// Preferred region order; the first region with capacity wins.
+    #[serde(default)]
+    pub regions: Vec<String>,
+    /// Private key path passed to `ssh -i` when auto-connecting.
+    pub ssh_private_key: Option<String>,
+    /// Profile-scoped API key, taking precedence over `LAMBDA_API_KEY`.
+    pub api_key: Option<String>,
+}
+
+impl Config {
+    /// Load configuration from the default path, returning an empty config
+    /// when the file is absent so the tool still works flag-only.
+    pub fn load() -> Self {
+        Self::load_from(Self::default_path())
+    }
+
+    /// Resolve `~/.config/lambda/config.toml`, honouring `XDG_CONFIG_HOME`.
+    pub fn default_path() -> Option<PathBuf> {
+        if let Ok(xdg) = env::var("XDG_CONFIG_HOME") {
+            return Some(PathBuf::from(xdg).join("lambda").join("config.toml"));
+        }
+        env::var("HOME")
+            .ok()
+            .map(|home| PathBuf::from(home).join(".config").join("lambda").join("config.toml"))
+    }
+
+    fn load_from(path: Option<PathBuf>) -> Self {
+        let Some(path) = path else {
+            return Config::default();
+        };
+        match fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str(&contents).unwrap_or_else(|e| {
+                eprintln!("Failed to parse {}: {}", path.display(), e);
+                Config::default()
+            }),
+            Err(_) => Config::default(),
+        }
+    }
+
+    /// Look up a profile by name.
+    pub fn profile(&self, name: &str) -> Option<&Profile> {
+        self.profiles.get(name)
+    }
+}
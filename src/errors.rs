@@ -0,0 +1,38 @@
+use serde::Deserialize;
+use thiserror::Error;
+
+/// Crate-level error type returned by every command function.
+#[derive(Debug, Error)]
+pub enum CliError {
+    /// The HTTP request could not be completed (DNS, connection, timeout).
+    #[error("network request failed: {0}")]
+    Network(#[from] reqwest::Error),
+
+    /// Lambda returned a structured error body for a non-2xx response.
+    #[error("API error [{code}]: {message}")]
+    Api { code: String, message: String },
+
+    /// A non-2xx response whose body was not the documented error shape.
+    #[error("unexpected HTTP status {status}: {body}")]
+    Http { status: u16, body: String },
+
+    /// The response body could not be decoded into the expected struct.
+    #[error("failed to decode response: {0}")]
+    Decode(String),
+
+    /// A command-level failure that is not tied to a single request.
+    #[error("{0}")]
+    Other(String),
+}
+
+/// Lambda's documented error envelope: `{ "error": { "code", "message" } }`.
+#[derive(Deserialize)]
+pub struct LambdaErrorBody {
+    pub error: LambdaError,
+}
+
+#[derive(Deserialize)]
+pub struct LambdaError {
+    pub code: Option<String>,
+    pub message: String,
+}
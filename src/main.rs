@@ -1,27 +1,52 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use colored::Colorize;
 use prettytable::{row, Table};
-use reqwest::blocking::Client;
+use reqwest::blocking::{Client, Response};
 use reqwest::header::AUTHORIZATION;
-use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::env;
 use std::thread;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use chrono::Local;
 use dotenv::dotenv;
 use crossterm::{execute, terminal::{Clear, ClearType}, cursor::MoveTo};
 use std::io::{stdout, Write};
 
+mod config;
+mod errors;
+mod notifier;
+
+use config::{Config, Notifications, Profile};
+use errors::{CliError, LambdaErrorBody};
+use notifier::{CommandNotifier, DesktopNotifier, Notifier, WebhookNotifier};
+
 /// Simple program to interact with Lambda Labs GPU cloud
 #[derive(Parser)]
 #[command(name = "lambda")]
 #[command(about = "A command-line tool for Lambda Labs cloud GPU API", long_about = None)]
 struct Cli {
+    /// Named profile from the config file to draw defaults from
+    #[arg(long, global = true)]
+    profile: Option<String>,
+    /// Output format for machine-readable commands
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Table)]
+    output: OutputFormat,
     #[command(subcommand)]
     command: Option<Commands>,
 }
 
+/// How structured command output is rendered to stdout.
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+enum OutputFormat {
+    /// Colored human-readable table
+    #[default]
+    Table,
+    /// Serialized JSON for piping into `jq`
+    Json,
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// List all available GPU instances
@@ -29,34 +54,74 @@ enum Commands {
     /// Start a GPU instance with the specified SSH key
     Start {
         #[arg(short, long)]
-        gpu: String,
+        gpu: Option<String>,
         #[arg(short, long)]
-        ssh: String,
+        ssh: Option<String>,
+        /// SSH into the instance once it becomes active
+        #[arg(long)]
+        ssh_into: bool,
+        /// Run a remote command non-interactively instead of an interactive shell
+        #[arg(long)]
+        exec: Option<String>,
+        /// Seconds to wait for the instance to become active
+        #[arg(long, default_value_t = 600)]
+        timeout: u64,
     },
     /// Stop a specified GPU instance
     Stop {
         #[arg(short, long)]
-        gpu: String,
+        gpu: Option<String>,
     },
     /// List all running GPU instances
     Running,
+    /// Show details for a single instance by id
+    Details {
+        #[arg(short, long)]
+        id: String,
+    },
     /// Continuously find and start a GPU instance when it becomes available
     Find {
         #[arg(short, long)]
-        gpu: String,
-        #[arg(short, long, default_value = "")]
-        ssh: String,
+        gpu: Option<String>,
+        #[arg(short, long)]
+        ssh: Option<String>,
         #[arg(short, long, default_value_t = 10)]
         sec: u64,
+        /// SSH into the instance once it becomes active
+        #[arg(long)]
+        ssh_into: bool,
+        /// Run a remote command non-interactively instead of an interactive shell
+        #[arg(long)]
+        exec: Option<String>,
+        /// Seconds to wait for the instance to become active
+        #[arg(long, default_value_t = 600)]
+        timeout: u64,
+        /// Send a desktop notification on capacity and activation
+        #[arg(long)]
+        notify_desktop: bool,
+        /// POST to a Slack/Discord-style webhook on capacity and activation
+        #[arg(long)]
+        notify_webhook: Option<String>,
+        /// Run a shell command hook on capacity and activation
+        #[arg(long)]
+        notify_command: Option<String>,
     },
 }
 
-#[derive(Deserialize, Debug)]
+/// Options controlling post-launch SSH behaviour.
+struct SshConnect {
+    /// Private key path passed to `ssh -i`, resolved from config.
+    private_key: Option<String>,
+    /// Optional remote command to run non-interactively.
+    exec: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
 struct ApiResponse<T> {
     data: T,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 struct Instance {
     id: Option<String>,
     status: Option<String>,
@@ -64,32 +129,32 @@ struct Instance {
     ssh_key_names: Option<Vec<String>>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 struct LaunchResponse {
     instance_ids: Vec<String>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 struct InstanceTypeResponse {
     instance_type: InstanceType,
     regions_with_capacity_available: Vec<Region>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 struct InstanceType {
     description: String,
     price_cents_per_hour: i32,
     specs: InstanceSpecs,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 struct InstanceSpecs {
     vcpus: u32,
     memory_gib: u32,
     storage_gib: u32,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 struct Region {
     name: String,
     description: String,
@@ -97,55 +162,180 @@ struct Region {
 
 fn main() {
     dotenv().ok();
-    let api_key = env::var("LAMBDA_API_KEY").expect("LAMBDA_API_KEY must be set");
     let client = Client::new();
 
     let cli = Cli::parse();
 
-    match &cli.command {
-        Some(Commands::List) => {
-            list_instances(&client, &api_key);
-        }
-        Some(Commands::Start { gpu, ssh }) => {
-            start_instance(&client, &api_key, gpu, ssh);
+    let config = Config::load();
+    let profile = cli.profile.as_deref().map(|name| {
+        config.profile(name).unwrap_or_else(|| {
+            // A typo'd profile name would otherwise silently fall back to env +
+            // flags, launching with the wrong region order / SSH key — fail hard.
+            eprintln!("Profile '{}' not found in config", name);
+            std::process::exit(1);
+        })
+    });
+
+    let api_key = profile
+        .and_then(|p| p.api_key.clone())
+        .or_else(|| env::var("LAMBDA_API_KEY").ok())
+        .unwrap_or_else(|| {
+            eprintln!("No API key found (set LAMBDA_API_KEY or an api_key in the profile)");
+            std::process::exit(1);
+        });
+
+    let result = match &cli.command {
+        Some(Commands::List) => list_instances(&client, &api_key, cli.output),
+        Some(Commands::Start { gpu, ssh, ssh_into, exec, timeout }) => {
+            let gpu = resolve_gpu(gpu, profile);
+            let ssh_keys = resolve_ssh(ssh, profile);
+            let ssh_opts = ssh_connect(*ssh_into, exec, profile);
+            start_instance(&client, &api_key, &gpu, &ssh_keys, region_preference(profile), ssh_opts.as_ref(), *timeout).map(|_| ())
         }
         Some(Commands::Stop { gpu }) => {
-            stop_instance(&client, &api_key, gpu);
+            let gpu = gpu.clone().unwrap_or_else(|| {
+                eprintln!("No instance id specified (use --gpu <instance-id>)");
+                std::process::exit(1);
+            });
+            stop_instance(&client, &api_key, &gpu)
         }
-        Some(Commands::Running) => {
-            list_running_instances(&client, &api_key);
+        Some(Commands::Running) => list_running_instances(&client, &api_key, cli.output),
+        Some(Commands::Details { id }) => show_instance_details(&client, &api_key, id, cli.output),
+        Some(Commands::Find { gpu, ssh, sec, ssh_into, exec, timeout, notify_desktop, notify_webhook, notify_command }) => {
+            let gpu = resolve_gpu(gpu, profile);
+            let ssh_keys = resolve_ssh(ssh, profile);
+            let ssh_opts = ssh_connect(*ssh_into, exec, profile);
+            let notifiers = build_notifiers(&client, &config.notifications, *notify_desktop, notify_webhook, notify_command);
+            find_and_start_instance(&client, &api_key, &gpu, &ssh_keys, region_preference(profile), *sec, ssh_opts.as_ref(), *timeout, &notifiers)
         }
-        Some(Commands::Find { gpu, ssh, sec }) => {
-            find_and_start_instance(&client, &api_key, gpu, ssh, *sec);
-        }
-        None => {
-            validate_api_key(&client, &api_key);
+        None => validate_api_key(&client, &api_key),
+    };
+
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Resolve the instance type with precedence: explicit flag > profile default.
+fn resolve_gpu(flag: &Option<String>, profile: Option<&Profile>) -> String {
+    flag.clone()
+        .or_else(|| profile.and_then(|p| p.instance_type.clone()))
+        .unwrap_or_else(|| {
+            eprintln!("No instance type specified (use --gpu or set instance_type in the profile)");
+            std::process::exit(1);
+        })
+}
+
+/// Resolve the SSH key names with precedence: explicit flag > profile defaults.
+fn resolve_ssh(flag: &Option<String>, profile: Option<&Profile>) -> Vec<String> {
+    if let Some(ssh) = flag {
+        return vec![ssh.clone()];
+    }
+    profile.map(|p| p.ssh_key_names.clone()).unwrap_or_default()
+}
+
+/// Preferred region order for the selected profile (empty when none set).
+fn region_preference(profile: Option<&Profile>) -> &[String] {
+    profile.map(|p| p.regions.as_slice()).unwrap_or(&[])
+}
+
+/// Assemble the notifier set for the `Find` watcher. Command-line flags take
+/// precedence over, and are merged with, the `[notifications]` config block.
+fn build_notifiers(client: &Client, config: &Notifications, desktop: bool, webhook: &Option<String>, command: &Option<String>) -> Vec<Box<dyn Notifier>> {
+    let mut notifiers: Vec<Box<dyn Notifier>> = Vec::new();
+
+    if desktop || config.desktop {
+        notifiers.push(Box::new(DesktopNotifier));
+    }
+    if let Some(url) = webhook.clone().or_else(|| config.webhook.clone()) {
+        notifiers.push(Box::new(WebhookNotifier::new(client.clone(), url)));
+    }
+    if let Some(cmd) = command.clone().or_else(|| config.command.clone()) {
+        notifiers.push(Box::new(CommandNotifier::new(cmd)));
+    }
+
+    notifiers
+}
+
+/// Fire a notification across every configured destination.
+fn notify_all(notifiers: &[Box<dyn Notifier>], title: &str, message: &str) {
+    for notifier in notifiers {
+        notifier.notify(title, message);
+    }
+}
+
+/// Build the post-launch SSH options, if the user asked to connect. An
+/// `--exec` command implies `--ssh-into`; the private key is taken from the
+/// active profile.
+fn ssh_connect(ssh_into: bool, exec: &Option<String>, profile: Option<&Profile>) -> Option<SshConnect> {
+    if !ssh_into && exec.is_none() {
+        return None;
+    }
+    Some(SshConnect {
+        private_key: profile.and_then(|p| p.ssh_private_key.clone()),
+        exec: exec.clone(),
+    })
+}
+
+/// Check a response's status and decode its body. Non-2xx responses are mapped
+/// to the parsed Lambda error envelope (`CliError::Api`) when it matches, or
+/// `CliError::Http` otherwise; decode failures become `CliError::Decode`.
+fn parse_response<T: DeserializeOwned>(response: Response) -> Result<T, CliError> {
+    let status = response.status();
+    let body = response.text()?;
+    if !status.is_success() {
+        if let Ok(parsed) = serde_json::from_str::<LambdaErrorBody>(&body) {
+            return Err(CliError::Api {
+                code: parsed.error.code.unwrap_or_else(|| status.as_u16().to_string()),
+                message: parsed.error.message,
+            });
         }
+        return Err(CliError::Http { status: status.as_u16(), body });
     }
+    serde_json::from_str::<T>(&body).map_err(|e| CliError::Decode(e.to_string()))
 }
 
-fn validate_api_key(client: &Client, api_key: &str) {
+/// Check a response's status without decoding the body, mapping non-2xx
+/// responses to `CliError` the same way `parse_response` does. Used for
+/// endpoints whose success body we don't need to inspect.
+fn ensure_success(response: Response) -> Result<(), CliError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(());
+    }
+    let body = response.text()?;
+    if let Ok(parsed) = serde_json::from_str::<LambdaErrorBody>(&body) {
+        return Err(CliError::Api {
+            code: parsed.error.code.unwrap_or_else(|| status.as_u16().to_string()),
+            message: parsed.error.message,
+        });
+    }
+    Err(CliError::Http { status: status.as_u16(), body })
+}
+
+fn validate_api_key(client: &Client, api_key: &str) -> Result<(), CliError> {
     let url = "https://cloud.lambdalabs.com/api/v1/instances";
     let response = client.get(url)
         .header(AUTHORIZATION, format!("Bearer {}", api_key))
-        .send()
-        .expect("Failed to validate API key");
+        .send()?;
 
-    if response.status().is_success() {
-        println!("API key is valid");
-    } else {
-        println!("Failed to validate API key: {}", response.status());
-    }
+    ensure_success(response)?;
+    eprintln!("API key is valid");
+    Ok(())
 }
 
-fn list_instances(client: &Client, api_key: &str) {
+fn list_instances(client: &Client, api_key: &str, output: OutputFormat) -> Result<(), CliError> {
     let url = "https://cloud.lambdalabs.com/api/v1/instance-types";
-    let response: ApiResponse<HashMap<String, InstanceTypeResponse>> = client.get(url)
+    let response = client.get(url)
         .header(AUTHORIZATION, format!("Bearer {}", api_key))
-        .send()
-        .expect("Failed to list instances")
-        .json()
-        .expect("Failed to parse response");
+        .send()?;
+    let response: ApiResponse<HashMap<String, InstanceTypeResponse>> = parse_response(response)?;
+
+    if output == OutputFormat::Json {
+        print_json(&response.data);
+        return Ok(());
+    }
 
     let mut table = Table::new();
     table.add_row(row!["Instance Type", "Description", "Price (cents/hour)", "vCPUs", "Memory (GiB)", "Storage (GiB)", "Available Regions"]);
@@ -170,90 +360,165 @@ fn list_instances(client: &Client, api_key: &str) {
     }
 
     table.printstd();
+    Ok(())
 }
 
-fn start_instance(client: &Client, api_key: &str, gpu: &str, ssh: &str) {
-    if let Some(instance_type_response) = get_instance_type_response(client, api_key, gpu) {
-        let region_name = &instance_type_response.regions_with_capacity_available[0].name;
+/// Outcome of a launch attempt. Distinguishes "nothing launched" from a launch
+/// that succeeded — whether or not the instance reached `active` before the
+/// timeout — so a watcher never relaunches an instance that is merely slow to
+/// activate.
+enum LaunchOutcome {
+    /// No launch happened: the type wasn't found or capacity vanished first.
+    NotLaunched,
+    /// Launch succeeded and the instance reached `active`, with this IP.
+    Active(String),
+    /// Launch succeeded but the instance didn't activate before the timeout.
+    TimedOut(String),
+}
 
-        let url = "https://cloud.lambdalabs.com/api/v1/instance-operations/launch";
-        let payload = serde_json::json!({
-            "region_name": region_name,
-            "instance_type_name": gpu,
-            "ssh_key_names": [ssh],
-            "quantity": 1
-        });
+fn start_instance(client: &Client, api_key: &str, gpu: &str, ssh_keys: &[String], region_pref: &[String], ssh_opts: Option<&SshConnect>, timeout: u64) -> Result<LaunchOutcome, CliError> {
+    let instance_type_response = match get_instance_type_response(client, api_key, gpu)? {
+        Some(itr) => itr,
+        None => {
+            eprintln!("Instance type {} not found.", gpu);
+            return Ok(LaunchOutcome::NotLaunched);
+        }
+    };
+    if instance_type_response.regions_with_capacity_available.is_empty() {
+        eprintln!("No regions with capacity available for {}.", gpu);
+        return Ok(LaunchOutcome::NotLaunched);
+    }
+    let region_name = select_region(&instance_type_response.regions_with_capacity_available, region_pref).to_string();
 
-        let response_result = client.post(url)
-            .header(AUTHORIZATION, format!("Bearer {}", api_key))
-            .json(&payload)
-            .send();
-
-        match response_result {
-            Ok(response) => {
-                let response_text = response.text().unwrap_or_else(|_| "Failed to read response text".to_string());
-                match serde_json::from_str::<ApiResponse<LaunchResponse>>(&response_text) {
-                    Ok(parsed_response) => {
-                        let instance_id = &parsed_response.data.instance_ids[0];
-                        println!("Instance {} started in region {}. Waiting for it to become active...", instance_id, region_name);
-
-                        std::thread::sleep(std::time::Duration::from_secs(120));
-
-                        let instance = get_instance_details(client, api_key, instance_id);
-                        match instance.ip {
-                            Some(ip) => println!("Instance is active. SSH IP: {}", ip),
-                            None => println!("Instance is active, but IP address is not available yet."),
-                        }
-                    }
-                    Err(e) => {
-                        println!("Failed to parse response: {}\nResponse text: {}", e, response_text);
-                    }
-                }
-            }
-            Err(e) => {
-                println!("Request failed: {}", e);
+    let url = "https://cloud.lambdalabs.com/api/v1/instance-operations/launch";
+    let payload = serde_json::json!({
+        "region_name": region_name,
+        "instance_type_name": gpu,
+        "ssh_key_names": ssh_keys,
+        "quantity": 1
+    });
+
+    let response = client.post(url)
+        .header(AUTHORIZATION, format!("Bearer {}", api_key))
+        .json(&payload)
+        .send()?;
+    let parsed: ApiResponse<LaunchResponse> = parse_response(response)?;
+    let instance_id = parsed.data.instance_ids.first().ok_or_else(|| {
+        CliError::Other(format!("launch of {} returned no instance id", gpu))
+    })?;
+    eprintln!("Instance {} started in region {}. Waiting for it to become active...", instance_id, region_name);
+
+    match wait_for_active(client, api_key, instance_id, timeout)? {
+        Some(instance) => {
+            let ip = instance.ip.unwrap_or_default();
+            eprintln!("Instance is active. SSH IP: {}", ip);
+            if let Some(opts) = ssh_opts {
+                ssh_into_instance(&ip, opts);
             }
+            Ok(LaunchOutcome::Active(ip))
+        }
+        None => {
+            eprintln!("Timed out after {}s waiting for instance {} to become active.", timeout, instance_id);
+            Ok(LaunchOutcome::TimedOut(instance_id.to_string()))
+        }
+    }
+}
+
+/// Poll `get_instance_details` until the instance is active with an IP, or the
+/// timeout elapses. Returns the active instance, or `None` on timeout.
+fn wait_for_active(client: &Client, api_key: &str, instance_id: &str, timeout: u64) -> Result<Option<Instance>, CliError> {
+    let deadline = Instant::now() + Duration::from_secs(timeout);
+    loop {
+        let instance = get_instance_details(client, api_key, instance_id)?;
+        if instance.status.as_deref() == Some("active") && instance.ip.is_some() {
+            return Ok(Some(instance));
+        }
+        if Instant::now() >= deadline {
+            return Ok(None);
         }
+        thread::sleep(Duration::from_secs(5));
+    }
+}
+
+/// Shell out to `ssh ubuntu@<ip>`, optionally with `-i <key>` and a remote
+/// command. Stdio is inherited so interactive sessions and `--exec` output
+/// both stream straight through.
+fn ssh_into_instance(ip: &str, opts: &SshConnect) {
+    let mut cmd = std::process::Command::new("ssh");
+    if let Some(key) = &opts.private_key {
+        cmd.arg("-i").arg(key);
+    }
+    cmd.arg(format!("ubuntu@{}", ip));
+    if let Some(exec) = &opts.exec {
+        eprintln!("Running `{}` on ubuntu@{}...", exec, ip);
+        cmd.arg(exec);
     } else {
-        println!("Instance type {} not found.", gpu);
+        eprintln!("Connecting to ubuntu@{}...", ip);
+    }
+
+    match cmd.status() {
+        Ok(status) if !status.success() => eprintln!("ssh exited with {}", status),
+        Ok(_) => {}
+        Err(e) => eprintln!("Failed to launch ssh: {}", e),
     }
 }
 
-fn get_instance_type_response(client: &Client, api_key: &str, gpu: &str) -> Option<InstanceTypeResponse> {
+/// Serialize a value as pretty JSON to stdout, keeping stdout clean for `jq`.
+fn print_json<T: Serialize>(value: &T) {
+    match serde_json::to_string_pretty(value) {
+        Ok(json) => println!("{}", json),
+        Err(e) => eprintln!("Failed to serialize output: {}", e),
+    }
+}
+
+/// Pick the first preferred region that currently has capacity, falling back
+/// to the first available region when none of the preferences match.
+fn select_region<'a>(available: &'a [Region], pref: &[String]) -> &'a str {
+    for want in pref {
+        if let Some(region) = available.iter().find(|r| &r.name == want) {
+            return &region.name;
+        }
+    }
+    &available[0].name
+}
+
+fn get_instance_type_response(client: &Client, api_key: &str, gpu: &str) -> Result<Option<InstanceTypeResponse>, CliError> {
     let url = "https://cloud.lambdalabs.com/api/v1/instance-types";
-    let response: ApiResponse<HashMap<String, InstanceTypeResponse>> = client.get(url)
+    let response = client.get(url)
         .header(AUTHORIZATION, format!("Bearer {}", api_key))
-        .send()
-        .expect("Failed to get instance types")
-        .json()
-        .expect("Failed to parse response");
+        .send()?;
+    let response: ApiResponse<HashMap<String, InstanceTypeResponse>> = parse_response(response)?;
 
-    response.data.get(gpu).cloned()
+    Ok(response.data.get(gpu).cloned())
 }
 
-fn stop_instance(client: &Client, api_key: &str, gpu: &str) {
+fn stop_instance(client: &Client, api_key: &str, gpu: &str) -> Result<(), CliError> {
     let url = "https://cloud.lambdalabs.com/api/v1/instance-operations/terminate";
     let payload = serde_json::json!({
         "instance_ids": [gpu]
     });
 
-    client.post(url)
+    let response = client.post(url)
         .header(AUTHORIZATION, format!("Bearer {}", api_key))
         .json(&payload)
-        .send()
-        .expect("Failed to stop instance");
+        .send()?;
+    ensure_success(response)?;
 
-    println!("Instance {} stopped", gpu);
+    eprintln!("Instance {} stopped", gpu);
+    Ok(())
 }
 
-fn list_running_instances(client: &Client, api_key: &str) {
+fn list_running_instances(client: &Client, api_key: &str, output: OutputFormat) -> Result<(), CliError> {
     let url = "https://cloud.lambdalabs.com/api/v1/instances";
-    let response: ApiResponse<Vec<Instance>> = client.get(url)
+    let response = client.get(url)
         .header(AUTHORIZATION, format!("Bearer {}", api_key))
-        .send()
-        .expect("Failed to list running instances")
-        .json()
-        .expect("Failed to parse response");
+        .send()?;
+    let response: ApiResponse<Vec<Instance>> = parse_response(response)?;
+
+    if output == OutputFormat::Json {
+        print_json(&response.data);
+        return Ok(());
+    }
 
     let mut table = Table::new();
     table.add_row(row!["Instance ID", "Status", "IP Address", "SSH Key Names"]);
@@ -268,66 +533,189 @@ fn list_running_instances(client: &Client, api_key: &str) {
     }
 
     table.printstd();
+    Ok(())
 }
 
-fn find_and_start_instance(client: &Client, api_key: &str, gpu: &str, ssh: &str, sec: u64) {
-    println!("Looking for available instances of type {}...", gpu);
-
-    loop {
-        let start_time = Instant::now();
-        let check_time = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
-
-        let mut table = Table::new();
-        table.add_row(row!["Last Checked", "Status", "Next Check In (s)"]);
+/// Upper bound on the exponential backoff delay after transient errors, before
+/// the up-to-25% jitter that `backoff_delay` adds on top.
+const BACKOFF_CAP_SECS: u64 = 300;
+
+#[allow(clippy::too_many_arguments)]
+fn find_and_start_instance(client: &Client, api_key: &str, gpu: &str, ssh_keys: &[String], region_pref: &[String], sec: u64, ssh_opts: Option<&SshConnect>, timeout: u64, notifiers: &[Box<dyn Notifier>]) -> Result<(), CliError> {
+    let types: Vec<String> = gpu
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+    eprintln!("Looking for available instances of type(s): {}", types.join(", "));
+
+    // The blocking `reqwest` client plus a single whole-map fetch per tick means
+    // there is no concurrency to orchestrate, so a plain sleep loop is enough.
+    {
+        // Last-seen availability per watched type, shown in the live table.
+        let mut last_seen: HashMap<String, String> = HashMap::new();
+        let mut failures: u32 = 0;
+
+        loop {
+            let check_time = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+
+            // The instance-types endpoint returns every type's capacity in one
+            // response, so fetch the whole map once per tick and look each
+            // watched type up locally rather than refetching it per type.
+            let map = fetch_instance_types(client, api_key);
+
+            // Walk types in priority order; launch the first with capacity.
+            let mut transient_error = false;
+            let mut launch: Option<(String, Vec<Region>)> = None;
+            match &map {
+                Ok(types_map) => {
+                    for ty in &types {
+                        match types_map.get(ty) {
+                            Some(itr) if !itr.regions_with_capacity_available.is_empty() => {
+                                let regions = itr.regions_with_capacity_available.clone();
+                                last_seen.insert(ty.clone(), regions.iter().map(|r| r.name.clone()).collect::<Vec<_>>().join(", "));
+                                if launch.is_none() {
+                                    launch = Some((ty.clone(), regions));
+                                }
+                            }
+                            _ => {
+                                last_seen.insert(ty.clone(), "none".to_string());
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    transient_error = true;
+                    for ty in &types {
+                        last_seen.insert(ty.clone(), format!("error: {}", e));
+                    }
+                }
+            }
 
-        if let Some(instance_type_response) = get_instance_type_response(client, api_key, gpu) {
-            if !instance_type_response.regions_with_capacity_available.is_empty() {
-                let regions: Vec<String> = instance_type_response.regions_with_capacity_available
+            if let Some((ty, regions)) = launch {
+                let region_names: Vec<String> = regions
                     .iter()
                     .map(|region| format!("{} ({})", region.name, region.description))
                     .collect();
+                eprintln!("Found available {} in region(s): {:?}", ty, region_names);
+                notify_all(notifiers, "Lambda: capacity available", &format!("{} available in {}", ty, region_names.join(", ")));
+                // Stop watching once an instance was actually created — even if
+                // activation polling timed out — so we never relaunch a real
+                // (paid) instance that is merely slow to reach `active`. Only a
+                // `NotLaunched` capacity-race/not-found result keeps the watch
+                // running.
+                match start_instance(client, api_key, &ty, ssh_keys, region_pref, ssh_opts, timeout)? {
+                    LaunchOutcome::Active(ip) => {
+                        notify_all(notifiers, "Lambda: instance active", &format!("{} active at {}", ty, ip));
+                        break;
+                    }
+                    LaunchOutcome::TimedOut(id) => {
+                        notify_all(notifiers, "Lambda: instance launched", &format!("{} launched as {} but not yet active", ty, id));
+                        break;
+                    }
+                    LaunchOutcome::NotLaunched => {
+                        eprintln!("Capacity for {} disappeared before launch; continuing to watch.", ty);
+                    }
+                }
+            }
 
-                println!("Found available {} in region(s): {:?}", gpu, regions);
-                start_instance(client, api_key, gpu, ssh);
-                break;
+            // Exponential backoff only after transient errors; otherwise the
+            // base interval keeps the watch responsive.
+            if transient_error {
+                failures = failures.saturating_add(1);
+            } else {
+                failures = 0;
+            }
+            let delay = backoff_delay(sec, failures, BACKOFF_CAP_SECS.max(sec));
+
+            let mut table = Table::new();
+            table.add_row(row!["Instance Type", "Last Seen Availability"]);
+            for ty in &types {
+                let status = last_seen.get(ty).cloned().unwrap_or_else(|| "checking...".to_string());
+                let cell = if status == "none" || status.starts_with("error:") || status == "checking..." {
+                    status.red()
+                } else {
+                    status.green()
+                };
+                table.add_row(row![ty.clone(), cell]);
             }
-        }
-        
-        let next_check_in = sec.saturating_sub(start_time.elapsed().as_secs());
-        table.add_row(row![
-            check_time,
-            "No available instances found".red(),
-            next_check_in.to_string().yellow()
-        ]);
 
-        // Clear the screen and print the updated table
-        execute!(stdout(), Clear(ClearType::All), MoveTo(0, 0)).unwrap();
-        table.printstd();
+            // A terminal-write failure (e.g. stdout redirected or closed) must
+            // not abort the watch; log it and keep polling.
+            if let Err(e) = execute!(stdout(), Clear(ClearType::All), MoveTo(0, 0)) {
+                eprintln!("Failed to refresh status display: {}", e);
+            }
+            eprintln!("Last checked {} — next check in {}s", check_time, delay.as_secs());
+            table.printstd();
 
-        thread::sleep(Duration::from_secs(next_check_in));
+            thread::sleep(delay);
+        }
     }
+    Ok(())
 }
 
-fn get_instance_details(client: &Client, api_key: &str, instance_id: &str) -> Instance {
+/// Fetch the whole instance-types map in one request, surfacing transient
+/// failures as `Err` so the watcher can back off instead of aborting. The
+/// watcher looks each watched type up in this map locally, so a priority list
+/// costs one fetch per tick rather than one per type.
+fn fetch_instance_types(client: &Client, api_key: &str) -> Result<HashMap<String, InstanceTypeResponse>, String> {
+    // Route through `parse_response` so a non-2xx response surfaces the parsed
+    // Lambda `{error:{code,message}}` reason; collapse the `CliError` to a
+    // `String` for the watcher's backoff display.
+    let fetch = || -> Result<HashMap<String, InstanceTypeResponse>, CliError> {
+        let url = "https://cloud.lambdalabs.com/api/v1/instance-types";
+        let response = client.get(url)
+            .header(AUTHORIZATION, format!("Bearer {}", api_key))
+            .send()?;
+        let parsed: ApiResponse<HashMap<String, InstanceTypeResponse>> = parse_response(response)?;
+        Ok(parsed.data)
+    };
+    fetch().map_err(|e| e.to_string())
+}
+
+/// Jittered exponential backoff: `base * 2^failures`, capped at `cap`, with up
+/// to 25% of wall-clock-derived jitter to spread out retries. Jitter avoids a
+/// dependency on `rand` by seeding off the current time's sub-second nanos.
+fn backoff_delay(base: u64, failures: u32, cap: u64) -> Duration {
+    let raw = base
+        .saturating_mul(1u64 << failures.min(16))
+        .min(cap)
+        .max(1);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    let jitter = raw as f64 * 0.25 * (nanos as f64 / 1_000_000_000.0);
+    Duration::from_secs_f64(raw as f64 + jitter)
+}
+
+fn get_instance_details(client: &Client, api_key: &str, instance_id: &str) -> Result<Instance, CliError> {
     let url = format!("https://cloud.lambdalabs.com/api/v1/instances/{}", instance_id);
-    let response_result = client.get(&url)
+    let response = client.get(&url)
         .header(AUTHORIZATION, format!("Bearer {}", api_key))
-        .send();
+        .send()?;
+    let parsed: ApiResponse<Instance> = parse_response(response)?;
+    Ok(parsed.data)
+}
 
-    match response_result {
-        Ok(response) => {
-            let response_text = response.text().unwrap_or_else(|_| "Failed to read response text".to_string());
-            match serde_json::from_str::<ApiResponse<Instance>>(&response_text) {
-                Ok(parsed_response) => parsed_response.data,
-                Err(e) => {
-                    println!("Failed to parse response: {}\nResponse text: {}", e, response_text);
-                    panic!("Failed to get instance details");
-                }
-            }
-        }
-        Err(e) => {
-            println!("Request failed: {}", e);
-            panic!("Failed to get instance details");
-        }
+/// Fetch a single instance and render it as either the colored table or
+/// serialized JSON, honoring the global `--output` flag.
+fn show_instance_details(client: &Client, api_key: &str, instance_id: &str, output: OutputFormat) -> Result<(), CliError> {
+    let instance = get_instance_details(client, api_key, instance_id)?;
+
+    if output == OutputFormat::Json {
+        print_json(&instance);
+        return Ok(());
     }
+
+    let mut table = Table::new();
+    table.add_row(row!["Instance ID", "Status", "IP Address", "SSH Key Names"]);
+    table.add_row(row![
+        instance.id.clone().unwrap_or_else(|| "N/A".to_string()).green(),
+        instance.status.clone().unwrap_or_else(|| "N/A".to_string()).yellow(),
+        instance.ip.clone().unwrap_or_else(|| "N/A".to_string()).blue(),
+        instance.ssh_key_names.clone().unwrap_or_else(|| vec!["N/A".to_string()]).join(", ").purple()
+    ]);
+    table.printstd();
+    Ok(())
 }
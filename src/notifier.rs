@@ -0,0 +1,72 @@
+use reqwest::blocking::Client;
+
+/// A notification destination fired by the `Find` watcher.
+pub trait Notifier {
+    /// Deliver a notification with a short title and a message body.
+    fn notify(&self, title: &str, message: &str);
+}
+
+/// Desktop notification via `notify-send`.
+pub struct DesktopNotifier;
+
+impl Notifier for DesktopNotifier {
+    fn notify(&self, title: &str, message: &str) {
+        let result = std::process::Command::new("notify-send")
+            .arg(title)
+            .arg(message)
+            .status();
+        if let Err(e) = result {
+            eprintln!("Failed to send desktop notification: {}", e);
+        }
+    }
+}
+
+/// Slack/Discord-style webhook: POSTs a JSON body to the configured URL.
+pub struct WebhookNotifier {
+    client: Client,
+    url: String,
+}
+
+impl WebhookNotifier {
+    pub fn new(client: Client, url: String) -> Self {
+        WebhookNotifier { client, url }
+    }
+}
+
+impl Notifier for WebhookNotifier {
+    fn notify(&self, title: &str, message: &str) {
+        let payload = serde_json::json!({
+            "text": format!("{}: {}", title, message),
+            "content": format!("{}: {}", title, message),
+        });
+        if let Err(e) = self.client.post(&self.url).json(&payload).send() {
+            eprintln!("Failed to POST webhook notification: {}", e);
+        }
+    }
+}
+
+/// Shell-command hook: runs a command with the title and message exposed as
+/// `$LAMBDA_NOTIFY_TITLE` and `$LAMBDA_NOTIFY_MESSAGE`.
+pub struct CommandNotifier {
+    command: String,
+}
+
+impl CommandNotifier {
+    pub fn new(command: String) -> Self {
+        CommandNotifier { command }
+    }
+}
+
+impl Notifier for CommandNotifier {
+    fn notify(&self, title: &str, message: &str) {
+        let result = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(&self.command)
+            .env("LAMBDA_NOTIFY_TITLE", title)
+            .env("LAMBDA_NOTIFY_MESSAGE", message)
+            .status();
+        if let Err(e) = result {
+            eprintln!("Failed to run notification command: {}", e);
+        }
+    }
+}